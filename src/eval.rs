@@ -0,0 +1,151 @@
+// SPDX-License-Identifier: ISC
+
+// A tiny evaluation + search, in the spirit of the inkwell engine, so the
+// viewer can show a live centipawn estimate the way lichess.org does. The
+// static evaluation is material + piece-square tables + a small mobility term,
+// always from the perspective of the side to move; a negamax search with
+// alpha-beta pruning looks a few plies ahead and the result feeds the eval bar.
+
+use crate::moves::{self, apply_move, opposite, pseudo_legal_moves};
+use fen::{BoardState, Color, PieceKind};
+
+/// Default search depth in plies. Shallow enough to stay responsive between
+/// feed updates, deep enough to catch a hanging piece or a short tactic.
+pub const DEFAULT_DEPTH: u32 = 3;
+
+// Scores at or beyond this magnitude encode "mate in N" rather than material.
+const MATE_SCORE: i32 = 1_000_000;
+
+// Centipawn clamp for anything reported to the UI.
+const EVAL_CLAMP: i32 = 2_000;
+
+fn material(kind: PieceKind) -> i32 {
+    match kind {
+        PieceKind::Pawn => 100,
+        PieceKind::Knight => 320,
+        PieceKind::Bishop => 330,
+        PieceKind::Rook => 500,
+        PieceKind::Queen => 900,
+        PieceKind::King => 0,
+    }
+}
+
+// Piece-square tables indexed exactly like `BoardState.pieces` (a8 first, h1
+// last) from White's point of view; Black reads them vertically mirrored.
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+fn piece_square(kind: PieceKind, color: Color, index: usize) -> i32 {
+    // Black pieces read the same table from the mirrored square.
+    let square = match color {
+        Color::White => index,
+        Color::Black => index ^ 56,
+    };
+    match kind {
+        PieceKind::Pawn => PAWN_TABLE[square],
+        PieceKind::Knight => KNIGHT_TABLE[square],
+        _ => 0,
+    }
+}
+
+/// Static evaluation from the perspective of `color`: positive favours it.
+fn evaluate(board: &BoardState, color: Color) -> i32 {
+    let mut score = 0;
+    for (index, square) in board.pieces.iter().enumerate() {
+        if let Some(piece) = square {
+            let value = material(piece.kind)
+                + piece_square(piece.kind, piece.color, index);
+            if piece.color == color {
+                score += value;
+            } else {
+                score -= value;
+            }
+        }
+    }
+
+    // A small mobility nudge: more pseudo-legal moves than the opponent is
+    // worth a few centipawns.
+    let mobility = pseudo_legal_moves(board, color).len() as i32
+        - pseudo_legal_moves(board, opposite(color)).len() as i32;
+    score + mobility
+}
+
+// Legal moves are the pseudo-legal ones that don't leave our own king attacked.
+fn legal_moves(board: &BoardState, color: Color) -> Vec<moves::Move> {
+    pseudo_legal_moves(board, color)
+        .into_iter()
+        .filter(|a_move| !moves::is_in_check(&apply_move(board, a_move), color))
+        .collect()
+}
+
+fn negamax(
+    board: &BoardState,
+    color: Color,
+    depth: u32,
+    mut alpha: i32,
+    beta: i32,
+) -> i32 {
+    if depth == 0 {
+        return evaluate(board, color);
+    }
+
+    let moves = legal_moves(board, color);
+    if moves.is_empty() {
+        // No legal move: mate (prefer faster mates) or stalemate.
+        return if moves::is_in_check(board, color) {
+            -MATE_SCORE - depth as i32
+        } else {
+            0
+        };
+    }
+
+    let mut best = i32::MIN + 1;
+    for a_move in moves {
+        let child = apply_move(board, &a_move);
+        let score =
+            -negamax(&child, opposite(color), depth - 1, -beta, -alpha);
+        if score > best {
+            best = score;
+        }
+        if best > alpha {
+            alpha = best;
+        }
+        if alpha >= beta {
+            break;
+        }
+    }
+    best
+}
+
+/// Search `board` to `depth` plies and return a clamped centipawn score from
+/// the perspective of `color`. Mate scores keep a large magnitude so the bar
+/// saturates.
+pub fn evaluate_position(board: &BoardState, color: Color, depth: u32) -> i32 {
+    let score = negamax(board, color, depth, i32::MIN + 1, i32::MAX - 1);
+    if score.abs() > MATE_SCORE / 2 {
+        score.signum() * EVAL_CLAMP
+    } else {
+        score.clamp(-EVAL_CLAMP, EVAL_CLAMP)
+    }
+}
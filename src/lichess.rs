@@ -1,17 +1,61 @@
 // SPDX-License-Identifier: ISC
 
+use crate::eval;
+use crate::moves::{self, square_index, GameStatus};
+use crate::pgn::{self, TagRoster};
 use curl::easy::{Handler, WriteError};
 use fen::{BoardState, Color, PieceKind};
 use notcurses::{Channel, Plane, Position, Rgb};
 use rand::Rng;
 use serde::Deserialize;
+use std::sync::{Arc, Mutex};
 use unicode_segmentation::UnicodeSegmentation;
 
+/// A shared handle to the game state, updated by the curl feed thread and read
+/// (and ticked) by the render loop.
+pub type SharedState = Arc<Mutex<GameState>>;
+
 struct PositionOffset {
     row: u32,
     column: u32,
 }
 
+// The feed sends only a board placement (the `featured` summary) or a placement
+// plus side-to-move (the `fen` updates) in its `fen` field. Pad it out to a full
+// six-field FEN that `fen::BoardState` accepts, preserving the side to move when
+// the feed provides it rather than forcing White.
+fn full_fen(feed_fen: &str) -> String {
+    let mut fields = feed_fen.split_whitespace();
+    let placement = fields.next().unwrap_or("");
+    let side = match fields.next() {
+        Some("b") => "b",
+        _ => "w",
+    };
+    // Variant channels stream variant placements: Crazyhouse appends a drop
+    // pocket ("…/RNBQKBNR[Qp]") and flags promoted pieces with a trailing '~'.
+    // Strip both so the underlying position still parses as a standard board.
+    let placement = match placement.split_once('[') {
+        Some((board, _)) => board,
+        None => placement,
+    };
+    let placement: String = placement.chars().filter(|&c| c != '~').collect();
+    format!("{placement} {side} - - 0 1")
+}
+
+// Format a clock value in whole seconds as mm:ss.
+fn format_clock(seconds: u32) -> String {
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+// Blend a highlight colour halfway into a base square colour.
+fn blend(base: (u8, u8, u8), tint: (u8, u8, u8)) -> Rgb {
+    Rgb::new(
+        ((base.0 as u16 + tint.0 as u16) / 2) as u8,
+        ((base.1 as u16 + tint.1 as u16) / 2) as u8,
+        ((base.2 as u16 + tint.2 as u16) / 2) as u8,
+    )
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum PlayerKind {
@@ -63,20 +107,33 @@ pub struct User {
     id: String,
 }
 
+/// The full state of the watched game. Kept free of any notcurses handle so it
+/// can live behind a `Mutex` and be shared between the feed thread and the
+/// render loop.
 #[derive(Debug)]
-pub struct LichessTV<'a> {
+pub struct GameState {
     players: Vec<Player>,
     last_move: String,
     board: BoardState,
     board_orientation: PlayerKind,
     white_clock: u32,
     black_clock: u32,
-    nc_board_plane: &'a mut Plane,
+    // Engine eval (centipawns, White's view) and position status, cached on each
+    // board update so the per-second render loop doesn't re-run the search and
+    // the mate sweep when nothing has changed.
+    eval_white_cp: i32,
+    status: GameStatus,
+    // The watched game's id and the SAN moves seen so far, so the game can be
+    // exported as PGN when the featured game switches. Local mate detection is
+    // not treated as authoritative (en passant is not generated), so the
+    // terminal position alone never triggers an export.
+    game_id: String,
+    moves: Vec<String>,
 }
 
-impl<'a> LichessTV<'a> {
-    pub fn new(plane: &mut Plane) -> LichessTV {
-        LichessTV {
+impl GameState {
+    pub fn new() -> GameState {
+        GameState {
             board: BoardState::from_fen(
                 "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
             )
@@ -86,28 +143,245 @@ impl<'a> LichessTV<'a> {
             black_clock: 0,
             players: Vec::new(),
             board_orientation: PlayerKind::White,
+            eval_white_cp: 0,
+            status: GameStatus::Normal,
+            game_id: String::new(),
+            moves: Vec::new(),
+        }
+    }
+
+    fn apply_feed(&mut self, featured_game: FeaturedTVGameFeed) {
+        match featured_game {
+            FeaturedTVGameFeed::FeaturedTVGameSummary(summary) => {
+                // Parse before touching any state so an unparseable frame (e.g.
+                // a variant FEN the board crate rejects) is dropped cleanly
+                // rather than panicking the shared-state feed thread.
+                let board = match BoardState::from_fen(&full_fen(&summary.fen)) {
+                    Ok(board) => board,
+                    Err(error) => {
+                        eprintln!("skipping summary frame: {error}");
+                        return;
+                    }
+                };
+                // The featured game switched: archive the one we were watching
+                // before its moves are overwritten. A repeated summary for the
+                // same game must not discard the moves recorded so far.
+                let switching = summary.id != self.game_id;
+                if switching && !self.moves.is_empty() {
+                    self.export_pgn();
+                }
+                self.board = board;
+                self.refresh_analysis();
+                self.board_orientation = summary.orientation;
+                self.players = summary.players;
+                // A fresh game (or channel) starts from the summary position:
+                // drop the previous game's last-move highlight and clocks so no
+                // stale state bleeds onto the new board.
+                self.last_move = String::new();
+                self.white_clock = 0;
+                self.black_clock = 0;
+                if switching {
+                    self.game_id = summary.id;
+                    self.moves.clear();
+                }
+            }
+            FeaturedTVGameFeed::FeaturedTVGameUpdate(update) => {
+                // Drop a frame we can't parse instead of unwrapping it, so one
+                // malformed (or variant) FEN doesn't kill the stream.
+                let board = match BoardState::from_fen(&full_fen(&update.fen)) {
+                    Ok(board) => board,
+                    Err(error) => {
+                        eprintln!("skipping update frame: {error}");
+                        return;
+                    }
+                };
+                // Record the move that produced this position, in SAN, against
+                // the board it was played on (before we overwrite it).
+                let mover = self.board.side_to_move;
+                let san = pgn::uci_to_san(&self.board, &update.last_move, mover);
+                self.board = board;
+                self.refresh_analysis();
+                if !update.last_move.is_empty() {
+                    self.moves.push(san);
+                }
+                self.last_move = update.last_move;
+                // Reset both clocks to the authoritative feed values; the tick
+                // loop counts down from here between updates.
+                self.white_clock = update.white_clock;
+                self.black_clock = update.black_clock;
+            }
+        }
+    }
+
+    // Recompute the cached engine eval and position status from the current
+    // board. Run once per feed update so the render loop can read them each
+    // second without repeating the negamax search and the mate sweep.
+    fn refresh_analysis(&mut self) {
+        let side_to_move = self.board.side_to_move;
+        let score = eval::evaluate_position(
+            &self.board,
+            side_to_move,
+            eval::DEFAULT_DEPTH,
+        );
+        // Negamax scores are from the side to move; cache White's view.
+        self.eval_white_cp = match side_to_move {
+            Color::White => score,
+            Color::Black => -score,
+        };
+        self.status = moves::game_status(&self.board, side_to_move);
+    }
+
+    // Build the Seven Tag Roster from the parsed players and hand the recorded
+    // SAN moves to the PGN writer. The file is named after the game id so each
+    // watched game lands in its own `.pgn`.
+    fn export_pgn(&self) {
+        if self.game_id.is_empty() || self.moves.is_empty() {
+            return;
+        }
+
+        // Read the result off the final position: a mate scores for the side
+        // that delivered it, a stalemate is a draw, anything else is still in
+        // progress.
+        let result = match moves::game_status(&self.board, self.board.side_to_move) {
+            GameStatus::Checkmate => match self.board.side_to_move {
+                Color::White => "0-1",
+                Color::Black => "1-0",
+            },
+            GameStatus::Stalemate => "1/2-1/2",
+            _ => "*",
+        };
+
+        let mut roster = TagRoster {
+            site: format!("https://lichess.org/{}", self.game_id),
+            result: String::from(result),
+            ..TagRoster::default()
+        };
+        for player in &self.players {
+            let title = player.user.title.clone();
+            match player.color {
+                PlayerKind::White => {
+                    roster.white = player.user.name.clone();
+                    roster.white_elo = player.rating;
+                    roster.white_title = title;
+                }
+                PlayerKind::Black => {
+                    roster.black = player.user.name.clone();
+                    roster.black_elo = player.rating;
+                    roster.black_title = title;
+                }
+            }
+        }
+
+        let path = std::path::PathBuf::from(format!("{}.pgn", self.game_id));
+        if let Err(error) = pgn::write_game(&path, &roster, &self.moves) {
+            eprintln!("failed to write pgn for {}: {error}", self.game_id);
+        }
+    }
+
+    /// Decrement the running clock by one second. Called once per second by the
+    /// render loop so the display keeps ticking between feed updates; the side
+    /// to move is read from the FEN.
+    pub fn tick(&mut self) {
+        match self.board.side_to_move {
+            Color::White => {
+                self.white_clock = self.white_clock.saturating_sub(1)
+            }
+            Color::Black => {
+                self.black_clock = self.black_clock.saturating_sub(1)
+            }
+        }
+    }
+}
+
+impl Default for GameState {
+    fn default() -> GameState {
+        GameState::new()
+    }
+}
+
+/// The curl handler: buffers the ndjson feed and folds each frame into the
+/// shared `GameState`. It no longer draws — rendering is driven by the tick
+/// loop so the clocks animate between moves.
+pub struct LichessTV {
+    state: SharedState,
+    // libcurl delivers the ndjson feed in arbitrary chunks and may split a
+    // multibyte codepoint across two callbacks, so the raw bytes are buffered
+    // here and only complete lines are decoded.
+    line_buffer: Vec<u8>,
+}
+
+impl LichessTV {
+    pub fn new(state: SharedState) -> LichessTV {
+        LichessTV {
+            state,
+            line_buffer: Vec::new(),
+        }
+    }
+}
+
+/// Draws the shared `GameState` onto a notcurses plane. Owns the plane, so it
+/// stays on the main thread where notcurses lives.
+pub struct Renderer<'a> {
+    state: SharedState,
+    nc_board_plane: &'a mut Plane,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new(state: SharedState, plane: &'a mut Plane) -> Renderer<'a> {
+        Renderer {
+            state,
             nc_board_plane: plane,
         }
     }
 
-    fn draw_chess_board(&mut self) {
+    /// Redraw the board, panels and eval bar from the current shared state.
+    pub fn render(&mut self) {
+        let state = self.state.clone();
+        let state = state.lock().unwrap();
+        self.nc_board_plane.into_ref_mut().erase();
+        self.draw_chess_board(&state);
+    }
+
+    fn draw_chess_board(&mut self, state: &GameState) {
         let mut pieces_board = self.nc_board_plane.new_child().unwrap();
 
         pieces_board.move_to(Position::from_xy(0, 0)).unwrap();
 
         let plane_size = self.nc_board_plane.size();
-        let mut position = PositionOffset {
-            row: plane_size.1 / 2 - 4,
-            column: plane_size.0 / 2 - 12,
-        };
+        let board_top = plane_size.1 / 2 - 3;
+        let board_left = plane_size.0 / 2 - 12;
+        let flip = matches!(state.board_orientation, PlayerKind::Black);
 
-        for (n, a_piece) in self.board.pieces.iter().enumerate() {
-            if n % 8 == 0 {
-                position.row = position.row + 1;
-                position.column = plane_size.0 / 2 - 12;
-            } else {
-                position.column = position.column + 3;
-            }
+        // The feed gives us no check/mate flag; use the status cached on the
+        // last board update and mark the side-to-move king's square.
+        let side_to_move = state.board.side_to_move;
+        let status = state.status;
+        let king_square = state
+            .board
+            .pieces
+            .iter()
+            .position(|square| match square {
+                Some(piece) => {
+                    piece.kind == PieceKind::King && piece.color == side_to_move
+                }
+                None => false,
+            });
+
+        // The last move (UCI, e.g. "e2e4" or "e7e8q"): source is the first two
+        // characters, destination the next two. Promotion suffix is ignored.
+        let last_move_from = square_index(
+            state.last_move.get(0..2).unwrap_or(""),
+        );
+        let last_move_to = square_index(state.last_move.get(2..4).unwrap_or(""));
+
+        for (n, a_piece) in state.board.pieces.iter().enumerate() {
+            // When Black has the board orientation, draw the position rotated
+            // 180° so Black sits at the bottom the way a spectator sees it.
+            let display = if flip { 63 - n } else { n };
+            let position = PositionOffset {
+                row: board_top + (display / 8) as u32,
+                column: board_left + (display % 8) as u32 * 3,
+            };
 
             let piece_character = match a_piece {
                 Some(piece) => match piece.kind {
@@ -140,10 +414,30 @@ impl<'a> LichessTV<'a> {
                 None => "   ",
             };
 
-            let channel = match (n + (n / 8)) & 1 == 0 {
-                true => Channel::from_rgb(Rgb::new(195, 160, 130)),
-                false => Channel::from_rgb(Rgb::new(242, 225, 195)),
+            let base = match (n + (n / 8)) & 1 == 0 {
+                true => (195, 160, 130),
+                false => (242, 225, 195),
+            };
+
+            // Blend a yellow-green tint over the squares the last move touched.
+            let rgb = if last_move_from == Some(n) || last_move_to == Some(n) {
+                blend(base, (170, 162, 58))
+            } else {
+                Rgb::new(base.0, base.1, base.2)
             };
+            let mut channel = Channel::from_rgb(rgb);
+
+            // Tint the king's square red when it is in check; a brighter red
+            // on mate, where the game is over.
+            if king_square == Some(n) {
+                channel = match status {
+                    GameStatus::Check => Channel::from_rgb(Rgb::new(200, 70, 70)),
+                    GameStatus::Checkmate => {
+                        Channel::from_rgb(Rgb::new(230, 30, 30))
+                    }
+                    _ => channel,
+                };
+            }
 
             pieces_board.set_bg(channel);
 
@@ -156,39 +450,183 @@ impl<'a> LichessTV<'a> {
                 .unwrap();
         }
 
+        // Spell out a terminal result under the board on mate.
+        if status == GameStatus::Checkmate {
+            pieces_board.set_bg(Channel::from_rgb(Rgb::new(230, 30, 30)));
+            pieces_board
+                .putstr_at_xy(
+                    Some(plane_size.0 / 2 - 12),
+                    Some(plane_size.1 / 2 + 5),
+                    " checkmate ",
+                )
+                .unwrap();
+        }
+
+        self.draw_eval_bar(state, plane_size, flip);
+        self.draw_player_panels(state, board_top, board_left, flip, side_to_move);
+
         pieces_board.render().unwrap();
         self.nc_board_plane.render().unwrap();
     }
+
+    // Name/title/rating/clock panels above and below the board. The player at
+    // the bottom is whoever the orientation puts there; the side to move's
+    // clock is emphasised with a leading marker.
+    fn draw_player_panels(
+        &mut self,
+        state: &GameState,
+        board_top: u32,
+        board_left: u32,
+        flip: bool,
+        side_to_move: Color,
+    ) {
+        let (bottom, top) = if flip {
+            (PlayerKind::Black, PlayerKind::White)
+        } else {
+            (PlayerKind::White, PlayerKind::Black)
+        };
+
+        let top_panel = self.panel_text(state, &top, side_to_move);
+        let bottom_panel = self.panel_text(state, &bottom, side_to_move);
+
+        let mut panels = self.nc_board_plane.new_child().unwrap();
+        panels.move_to(Position::from_xy(0, 0)).unwrap();
+        panels.set_bg(Channel::from_rgb(Rgb::new(40, 40, 40)));
+
+        if let Some(text) = top_panel {
+            panels
+                .putstr_at_xy(Some(board_left), Some(board_top - 2), &text)
+                .unwrap();
+        }
+        if let Some(text) = bottom_panel {
+            panels
+                .putstr_at_xy(Some(board_left), Some(board_top + 9), &text)
+                .unwrap();
+        }
+
+        panels.render().unwrap();
+    }
+
+    // One panel line for the given side, or `None` if that player isn't known.
+    fn panel_text(
+        &self,
+        state: &GameState,
+        side: &PlayerKind,
+        side_to_move: Color,
+    ) -> Option<String> {
+        let color = match side {
+            PlayerKind::White => Color::White,
+            PlayerKind::Black => Color::Black,
+        };
+        let clock = match color {
+            Color::White => state.white_clock,
+            Color::Black => state.black_clock,
+        };
+
+        let player = state.players.iter().find(|player| {
+            matches!(
+                (&player.color, color),
+                (PlayerKind::White, Color::White)
+                    | (PlayerKind::Black, Color::Black)
+            )
+        })?;
+
+        let title = match &player.user.title {
+            Some(title) => format!("{title} "),
+            None => String::new(),
+        };
+        // A leading marker shows whose clock is running.
+        let marker = if color == side_to_move { "> " } else { "  " };
+
+        Some(format!(
+            "{marker}{title}{} ({})  {}",
+            player.user.name,
+            player.rating,
+            format_clock(clock)
+        ))
+    }
+
+    // A vertical bar to the right of the board: the White-fill proportion grows
+    // from the bottom with White's advantage, shrinking as Black takes over.
+    fn draw_eval_bar(
+        &mut self,
+        state: &GameState,
+        plane_size: (u32, u32),
+        flip: bool,
+    ) {
+        const BAR_HEIGHT: u32 = 8;
+
+        // The score is cached on each board update; the bar wants White's view.
+        let white_score = state.eval_white_cp;
+
+        // Map the clamped [-2000, 2000] centipawn range onto [0, 1].
+        let proportion = (white_score as f32 + 2000.0) / 4000.0;
+        let proportion = proportion.clamp(0.0, 1.0);
+        let white_rows = (proportion * BAR_HEIGHT as f32).round() as u32;
+
+        let mut bar = self.nc_board_plane.new_child().unwrap();
+        bar.move_to(Position::from_xy(0, 0)).unwrap();
+
+        let top = plane_size.1 / 2 - 3;
+        let column = plane_size.0 / 2 + 14;
+        for cell in 0..BAR_HEIGHT {
+            // White normally fills from the bottom; when the board is flipped
+            // for Black the bar flips with it so it still matches the side it
+            // sits beside.
+            let is_white = if flip {
+                cell < white_rows
+            } else {
+                cell >= BAR_HEIGHT - white_rows
+            };
+            let channel = match is_white {
+                true => Channel::from_rgb(Rgb::new(242, 225, 195)),
+                false => Channel::from_rgb(Rgb::new(60, 60, 60)),
+            };
+            bar.set_bg(channel);
+            bar.putstr_at_xy(Some(column), Some(top + cell), "  ")
+                .unwrap();
+        }
+
+        bar.render().unwrap();
+    }
 }
 
-impl<'a> Handler for LichessTV<'a> {
+impl Handler for LichessTV {
     fn write(&mut self, data: &[u8]) -> Result<usize, WriteError> {
-        let json_data = std::str::from_utf8(data).unwrap();
+        // libcurl hands us whatever bytes arrived: a partial line, a single
+        // line, or several lines at once, and a multibyte codepoint may straddle
+        // two callbacks. Buffer the raw bytes and decode only complete lines so
+        // no non-ASCII player name is corrupted.
+        self.line_buffer.extend_from_slice(data);
 
-        let featured_game: FeaturedTVGameFeed =
-            serde_json::from_str(json_data).unwrap();
+        while let Some(newline) =
+            self.line_buffer.iter().position(|&byte| byte == b'\n')
+        {
+            let line: Vec<u8> = self.line_buffer.drain(..=newline).collect();
 
-        match featured_game {
-            FeaturedTVGameFeed::FeaturedTVGameSummary(summary) => {
-                let mut patched_fen = String::from(summary.fen);
-                patched_fen.push_str(" w c - 1 1");
-                self.board = BoardState::from_fen(&patched_fen).unwrap();
-                self.board_orientation = summary.orientation;
-                self.players = summary.players;
+            // A line that isn't valid UTF-8 can't be a feed frame; drop it.
+            let line = match std::str::from_utf8(&line) {
+                Ok(line) => line.trim(),
+                Err(error) => {
+                    eprintln!("skipping non-utf8 feed line: {error}");
+                    continue;
+                }
+            };
+
+            // Lichess emits blank keep-alive frames; nothing to parse.
+            if line.is_empty() {
+                continue;
             }
-            FeaturedTVGameFeed::FeaturedTVGameUpdate(update) => {
-                let mut patched_fen = String::from(update.fen);
-                patched_fen.push_str(" c - 1 1");
-                self.board = BoardState::from_fen(&patched_fen).unwrap();
-                self.last_move = update.last_move;
-                self.white_clock = update.white_clock;
-                self.black_clock = update.black_clock;
+
+            match serde_json::from_str::<FeaturedTVGameFeed>(line) {
+                Ok(featured_game) => {
+                    self.state.lock().unwrap().apply_feed(featured_game)
+                }
+                // A single malformed frame shouldn't tear down the stream.
+                Err(error) => eprintln!("failed to parse feed frame: {error}"),
             }
         }
 
-        self.nc_board_plane.into_ref_mut().erase();
-        self.draw_chess_board();
-
         Ok(data.len())
     }
 }
@@ -0,0 +1,412 @@
+// SPDX-License-Identifier: ISC
+
+// A small, self-contained legality engine operating directly on the
+// `fen::BoardState.pieces` array. The feed only gives us a FEN and the last
+// move, so this module reconstructs whether the side to move is in check,
+// checkmate or stalemate by brute force: attack tests for check, and a
+// pseudo-legal move generator filtered by "does this leave my king attacked?"
+// for mate/stalemate. It mirrors the knight/ray/pawn attack logic used by the
+// asonix chess-server rather than pulling in a full chess crate.
+
+use fen::{BoardState, Color, Piece, PieceKind};
+
+// Knight move offsets as (row, column) deltas.
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
+
+// The eight squares surrounding a king.
+const KING_OFFSETS: [(i32, i32); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+const DIAGONAL_RAYS: [(i32, i32); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+const ORTHOGONAL_RAYS: [(i32, i32); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+
+/// The broad state of a position from the perspective of the side to move.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GameStatus {
+    Normal,
+    Check,
+    Checkmate,
+    Stalemate,
+}
+
+/// A pseudo-legal move: indices into `BoardState.pieces`, plus an optional
+/// promotion piece parsed from a UCI suffix.
+#[derive(Debug, Clone, Copy)]
+pub struct Move {
+    pub from: usize,
+    pub to: usize,
+    pub promotion: Option<PieceKind>,
+}
+
+// Board indices run a8..h8, a7..h7, ... a1..h1 (rank 8 first), matching the
+// order `fen` fills `pieces`. Row 0 is rank 8, column 0 is the a-file.
+fn index(row: i32, column: i32) -> usize {
+    (row * 8 + column) as usize
+}
+
+fn on_board(row: i32, column: i32) -> bool {
+    (0..8).contains(&row) && (0..8).contains(&column)
+}
+
+fn piece_at(board: &BoardState, row: i32, column: i32) -> Option<&Piece> {
+    board.pieces[index(row, column)].as_ref()
+}
+
+fn king_square(board: &BoardState, color: Color) -> Option<(i32, i32)> {
+    for row in 0..8 {
+        for column in 0..8 {
+            if let Some(piece) = piece_at(board, row, column) {
+                if piece.kind == PieceKind::King && piece.color == color {
+                    return Some((row, column));
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Does an enemy piece attack the square occupied by `color`'s king?
+pub fn is_in_check(board: &BoardState, color: Color) -> bool {
+    let Some((king_row, king_column)) = king_square(board, color) else {
+        // No king on the board (shouldn't happen in a real game) — treat as
+        // not in check so we never report a spurious mate.
+        return false;
+    };
+
+    let enemy = opposite(color);
+
+    // Knights.
+    for (dr, dc) in KNIGHT_OFFSETS {
+        let (r, c) = (king_row + dr, king_column + dc);
+        if on_board(r, c) {
+            if let Some(piece) = piece_at(board, r, c) {
+                if piece.color == enemy && piece.kind == PieceKind::Knight {
+                    return true;
+                }
+            }
+        }
+    }
+
+    // The enemy king (relevant when generating king moves).
+    for (dr, dc) in KING_OFFSETS {
+        let (r, c) = (king_row + dr, king_column + dc);
+        if on_board(r, c) {
+            if let Some(piece) = piece_at(board, r, c) {
+                if piece.color == enemy && piece.kind == PieceKind::King {
+                    return true;
+                }
+            }
+        }
+    }
+
+    // Sliding pieces along the diagonals: bishop or queen.
+    if ray_attacked(
+        board,
+        king_row,
+        king_column,
+        &DIAGONAL_RAYS,
+        enemy,
+        PieceKind::Bishop,
+    ) {
+        return true;
+    }
+
+    // Sliding pieces along the files and ranks: rook or queen.
+    if ray_attacked(
+        board,
+        king_row,
+        king_column,
+        &ORTHOGONAL_RAYS,
+        enemy,
+        PieceKind::Rook,
+    ) {
+        return true;
+    }
+
+    // Pawns. A white pawn attacks "upward" (toward rank 8, decreasing row), so
+    // it checks a king sitting one row above it; a black pawn the reverse.
+    let pawn_row = match enemy {
+        Color::White => king_row + 1,
+        Color::Black => king_row - 1,
+    };
+    for dc in [-1, 1] {
+        let c = king_column + dc;
+        if on_board(pawn_row, c) {
+            if let Some(piece) = piece_at(board, pawn_row, c) {
+                if piece.color == enemy && piece.kind == PieceKind::Pawn {
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+// Walk each ray until a piece blocks it; flag an attack when the first piece
+// met is an enemy queen or the given straight-line attacker kind.
+fn ray_attacked(
+    board: &BoardState,
+    king_row: i32,
+    king_column: i32,
+    rays: &[(i32, i32); 4],
+    enemy: Color,
+    slider: PieceKind,
+) -> bool {
+    for (dr, dc) in *rays {
+        let (mut r, mut c) = (king_row + dr, king_column + dc);
+        while on_board(r, c) {
+            if let Some(piece) = piece_at(board, r, c) {
+                if piece.color == enemy
+                    && (piece.kind == slider || piece.kind == PieceKind::Queen)
+                {
+                    return true;
+                }
+                break;
+            }
+            r += dr;
+            c += dc;
+        }
+    }
+    false
+}
+
+/// The colour that isn't `color`.
+pub(crate) fn opposite(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+/// Parse a UCI coordinate like `e4` into an index into `BoardState.pieces`
+/// (a8 first, h1 last). Returns `None` for anything that isn't file+rank.
+pub(crate) fn square_index(square: &str) -> Option<usize> {
+    let mut chars = square.chars();
+    let file = chars.next()?;
+    let rank = chars.next()?;
+    let column = match file {
+        'a'..='h' => file as usize - 'a' as usize,
+        _ => return None,
+    };
+    let rank = rank.to_digit(10)?;
+    if !(1..=8).contains(&rank) {
+        return None;
+    }
+    Some((8 - rank as usize) * 8 + column)
+}
+
+/// Generate every pseudo-legal move for `color`. Castling is omitted because it
+/// can never escape an existing check. En passant is also omitted, but that is
+/// a genuine limitation rather than a free pass: capturing a checking pawn en
+/// passant *does* lift the check, so a `game_status` verdict can be wrong in the
+/// rare position whose only legal reply is an en-passant capture — and the feed
+/// gives us no en-passant square to generate it from anyway.
+pub fn pseudo_legal_moves(board: &BoardState, color: Color) -> Vec<Move> {
+    let mut moves = Vec::new();
+
+    for row in 0..8 {
+        for column in 0..8 {
+            let Some(piece) = piece_at(board, row, column) else {
+                continue;
+            };
+            if piece.color != color {
+                continue;
+            }
+
+            match piece.kind {
+                PieceKind::Pawn => pawn_moves(board, color, row, column, &mut moves),
+                PieceKind::Knight => {
+                    for (dr, dc) in KNIGHT_OFFSETS {
+                        add_step(board, color, row, column, dr, dc, &mut moves);
+                    }
+                }
+                PieceKind::King => {
+                    for (dr, dc) in KING_OFFSETS {
+                        add_step(board, color, row, column, dr, dc, &mut moves);
+                    }
+                }
+                PieceKind::Bishop => {
+                    add_slides(board, color, row, column, &DIAGONAL_RAYS, &mut moves)
+                }
+                PieceKind::Rook => {
+                    add_slides(board, color, row, column, &ORTHOGONAL_RAYS, &mut moves)
+                }
+                PieceKind::Queen => {
+                    add_slides(board, color, row, column, &DIAGONAL_RAYS, &mut moves);
+                    add_slides(board, color, row, column, &ORTHOGONAL_RAYS, &mut moves);
+                }
+            }
+        }
+    }
+
+    moves
+}
+
+// A single non-sliding step (knight/king): legal onto an empty square or an
+// enemy capture.
+fn add_step(
+    board: &BoardState,
+    color: Color,
+    row: i32,
+    column: i32,
+    dr: i32,
+    dc: i32,
+    moves: &mut Vec<Move>,
+) {
+    let (r, c) = (row + dr, column + dc);
+    if !on_board(r, c) {
+        return;
+    }
+    match piece_at(board, r, c) {
+        Some(piece) if piece.color == color => {}
+        _ => moves.push(Move {
+            from: index(row, column),
+            to: index(r, c),
+            promotion: None,
+        }),
+    }
+}
+
+fn add_slides(
+    board: &BoardState,
+    color: Color,
+    row: i32,
+    column: i32,
+    rays: &[(i32, i32); 4],
+    moves: &mut Vec<Move>,
+) {
+    for (dr, dc) in *rays {
+        let (mut r, mut c) = (row + dr, column + dc);
+        while on_board(r, c) {
+            match piece_at(board, r, c) {
+                Some(piece) => {
+                    if piece.color != color {
+                        moves.push(Move {
+                            from: index(row, column),
+                            to: index(r, c),
+                            promotion: None,
+                        });
+                    }
+                    break;
+                }
+                None => moves.push(Move {
+                    from: index(row, column),
+                    to: index(r, c),
+                    promotion: None,
+                }),
+            }
+            r += dr;
+            c += dc;
+        }
+    }
+}
+
+fn pawn_moves(
+    board: &BoardState,
+    color: Color,
+    row: i32,
+    column: i32,
+    moves: &mut Vec<Move>,
+) {
+    // White advances toward rank 8 (row decreasing); Black the other way.
+    let (forward, start_row, promote_row) = match color {
+        Color::White => (-1, 6, 0),
+        Color::Black => (1, 1, 7),
+    };
+
+    // Single and double pushes onto empty squares.
+    let one = row + forward;
+    if on_board(one, column) && piece_at(board, one, column).is_none() {
+        push_pawn(row, column, one, column, one == promote_row, moves);
+        let two = row + 2 * forward;
+        if row == start_row
+            && on_board(two, column)
+            && piece_at(board, two, column).is_none()
+        {
+            push_pawn(row, column, two, column, false, moves);
+        }
+    }
+
+    // Diagonal captures.
+    for dc in [-1, 1] {
+        let c = column + dc;
+        if on_board(one, c) {
+            if let Some(piece) = piece_at(board, one, c) {
+                if piece.color == opposite(color) {
+                    push_pawn(row, column, one, c, one == promote_row, moves);
+                }
+            }
+        }
+    }
+}
+
+fn push_pawn(
+    from_row: i32,
+    from_column: i32,
+    to_row: i32,
+    to_column: i32,
+    promotion: bool,
+    moves: &mut Vec<Move>,
+) {
+    let from = index(from_row, from_column);
+    let to = index(to_row, to_column);
+    if promotion {
+        // Only the queen matters for a legality/mate verdict.
+        moves.push(Move {
+            from,
+            to,
+            promotion: Some(PieceKind::Queen),
+        });
+    } else {
+        moves.push(Move {
+            from,
+            to,
+            promotion: None,
+        });
+    }
+}
+
+/// Apply a pseudo-legal move to a cloned board, returning the new position.
+pub fn apply_move(board: &BoardState, a_move: &Move) -> BoardState {
+    let mut next = board.clone();
+    let mut piece = next.pieces[a_move.from].take();
+    if let (Some(piece), Some(kind)) = (piece.as_mut(), a_move.promotion) {
+        piece.kind = kind;
+    }
+    next.pieces[a_move.to] = piece;
+    next
+}
+
+/// Classify the position for the side to move.
+pub fn game_status(board: &BoardState, color: Color) -> GameStatus {
+    let in_check = is_in_check(board, color);
+
+    let has_escape = pseudo_legal_moves(board, color).iter().any(|a_move| {
+        !is_in_check(&apply_move(board, a_move), color)
+    });
+
+    match (in_check, has_escape) {
+        (true, true) => GameStatus::Check,
+        (true, false) => GameStatus::Checkmate,
+        (false, false) => GameStatus::Stalemate,
+        (false, true) => GameStatus::Normal,
+    }
+}
@@ -0,0 +1,259 @@
+// SPDX-License-Identifier: ISC
+
+// The feed hands us a stream of UCI moves and nothing else, so this module
+// turns the game the viewer just watched into a portable record. Each
+// `last_move` is replayed against the board it was played on, converted to SAN
+// by comparing the before/after `BoardState.pieces` (captures, castling,
+// promotion and disambiguation), and the accumulated move list is written out
+// with the Seven Tag Roster filled from the player data already parsed off the
+// feed.
+
+use crate::moves::{self, apply_move, opposite, pseudo_legal_moves, square_index};
+use fen::{BoardState, Color, PieceKind};
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// The Seven Tag Roster plus the ratings/titles the feed gives us. Empty
+/// strings fall back to the PGN "unknown" placeholders when written.
+#[derive(Debug, Default)]
+pub struct TagRoster {
+    pub site: String,
+    pub white: String,
+    pub black: String,
+    pub result: String,
+    pub white_elo: u32,
+    pub black_elo: u32,
+    pub white_title: Option<String>,
+    pub black_title: Option<String>,
+}
+
+// Algebraic name ("e4") of a `BoardState.pieces` index (a8 first, h1 last).
+fn square_name(index: usize) -> String {
+    let file = (b'a' + (index % 8) as u8) as char;
+    let rank = 8 - (index / 8);
+    format!("{file}{rank}")
+}
+
+fn piece_letter(kind: PieceKind) -> &'static str {
+    match kind {
+        PieceKind::Pawn => "",
+        PieceKind::Knight => "N",
+        PieceKind::Bishop => "B",
+        PieceKind::Rook => "R",
+        PieceKind::Queen => "Q",
+        PieceKind::King => "K",
+    }
+}
+
+/// Convert one UCI move (e.g. `e2e4`, `e7e8q`, `e1g1`) played by `mover` on
+/// `before` into SAN. Returns the raw UCI string unchanged if it can't be
+/// parsed, so a stray frame never corrupts the rest of the record.
+pub fn uci_to_san(before: &BoardState, uci: &str, mover: Color) -> String {
+    let (Some(from), Some(to)) = (
+        uci.get(0..2).and_then(square_index),
+        uci.get(2..4).and_then(square_index),
+    )
+    else {
+        return uci.to_string();
+    };
+    let promotion = uci.chars().nth(4).and_then(promotion_kind);
+
+    let Some(piece) = before.pieces[from].as_ref() else {
+        return uci.to_string();
+    };
+
+    let from_file = from % 8;
+    let to_file = to % 8;
+    let captured = before.pieces[to].is_some()
+        || (piece.kind == PieceKind::Pawn && from_file != to_file);
+
+    // Castling is a two-file king step; the rook move is implicit in SAN.
+    if piece.kind == PieceKind::King && from_file.abs_diff(to_file) == 2 {
+        let castle = if to_file > from_file { "O-O" } else { "O-O-O" };
+        return with_suffixes(before, uci, mover, castle.to_string());
+    }
+
+    let mut san = String::new();
+    san.push_str(piece_letter(piece.kind));
+
+    if piece.kind == PieceKind::Pawn {
+        // A capturing pawn is written with its source file ("exd5").
+        if captured {
+            san.push((b'a' + from_file as u8) as char);
+        }
+    } else {
+        san.push_str(&disambiguation(before, mover, piece.kind, from, to));
+    }
+
+    if captured {
+        san.push('x');
+    }
+    san.push_str(&square_name(to));
+
+    if let Some(kind) = promotion {
+        san.push('=');
+        san.push_str(piece_letter(kind));
+    }
+
+    with_suffixes(before, uci, mover, san)
+}
+
+// Append "+"/"#" by applying the move and inspecting the opponent.
+fn with_suffixes(
+    before: &BoardState,
+    uci: &str,
+    mover: Color,
+    san: String,
+) -> String {
+    let (Some(from), Some(to)) = (
+        uci.get(0..2).and_then(square_index),
+        uci.get(2..4).and_then(square_index),
+    )
+    else {
+        return san;
+    };
+    let a_move = moves::Move {
+        from,
+        to,
+        promotion: uci.chars().nth(4).and_then(promotion_kind),
+    };
+    let mut after = apply_move(before, &a_move);
+
+    // `apply_move` only slides the king for a castle; relocate the rook too so a
+    // check or mate delivered by the castled rook keeps its "+"/"#" suffix.
+    let is_king = before.pieces[from]
+        .as_ref()
+        .map(|piece| piece.kind == PieceKind::King)
+        .unwrap_or(false);
+    if is_king && (from % 8).abs_diff(to % 8) == 2 {
+        let rank = from - from % 8;
+        let (rook_from, rook_to) = if to % 8 > from % 8 {
+            (rank + 7, rank + 5)
+        } else {
+            (rank, rank + 3)
+        };
+        after.pieces[rook_to] = after.pieces[rook_from].take();
+    }
+
+    let opponent = opposite(mover);
+    match moves::game_status(&after, opponent) {
+        moves::GameStatus::Checkmate => format!("{san}#"),
+        moves::GameStatus::Check => format!("{san}+"),
+        _ => san,
+    }
+}
+
+// When another piece of the same kind could also reach `to`, qualify the
+// source square by file, then rank, then both.
+fn disambiguation(
+    before: &BoardState,
+    mover: Color,
+    kind: PieceKind,
+    from: usize,
+    to: usize,
+) -> String {
+    let rivals: Vec<usize> = pseudo_legal_moves(before, mover)
+        .into_iter()
+        .filter(|a_move| a_move.to == to && a_move.from != from)
+        // A pinned rival can't legally reach the square, so it neither forces
+        // nor masks a disambiguation.
+        .filter(|a_move| !moves::is_in_check(&apply_move(before, a_move), mover))
+        .map(|a_move| a_move.from)
+        .filter(|&other| {
+            before.pieces[other]
+                .as_ref()
+                .map(|piece| piece.kind == kind)
+                .unwrap_or(false)
+        })
+        .collect();
+
+    if rivals.is_empty() {
+        return String::new();
+    }
+
+    let same_file = rivals.iter().any(|&other| other % 8 == from % 8);
+    let same_rank = rivals.iter().any(|&other| other / 8 == from / 8);
+    let name = square_name(from);
+    let file = &name[0..1];
+    let rank = &name[1..2];
+
+    if !same_file {
+        file.to_string()
+    } else if !same_rank {
+        rank.to_string()
+    } else {
+        name
+    }
+}
+
+fn promotion_kind(letter: char) -> Option<PieceKind> {
+    match letter.to_ascii_lowercase() {
+        'n' => Some(PieceKind::Knight),
+        'b' => Some(PieceKind::Bishop),
+        'r' => Some(PieceKind::Rook),
+        'q' => Some(PieceKind::Queen),
+        _ => None,
+    }
+}
+
+/// Write the recorded game to `path` as a standard PGN. The move text is wrapped
+/// in the conventional `1. e4 e5` numbering, ending with the result token.
+pub fn write_game(
+    path: &Path,
+    roster: &TagRoster,
+    san_moves: &[String],
+) -> io::Result<()> {
+    let mut file = File::create(path)?;
+
+    let site = or_unknown(&roster.site);
+    let white = or_unknown(&roster.white);
+    let black = or_unknown(&roster.black);
+    let result = if roster.result.is_empty() {
+        "*"
+    } else {
+        &roster.result
+    };
+
+    writeln!(file, "[Event \"Lichess TV\"]")?;
+    writeln!(file, "[Site \"{site}\"]")?;
+    writeln!(file, "[Date \"????.??.??\"]")?;
+    writeln!(file, "[Round \"-\"]")?;
+    writeln!(file, "[White \"{white}\"]")?;
+    writeln!(file, "[Black \"{black}\"]")?;
+    writeln!(file, "[Result \"{result}\"]")?;
+    if roster.white_elo > 0 {
+        writeln!(file, "[WhiteElo \"{}\"]", roster.white_elo)?;
+    }
+    if roster.black_elo > 0 {
+        writeln!(file, "[BlackElo \"{}\"]", roster.black_elo)?;
+    }
+    if let Some(title) = &roster.white_title {
+        writeln!(file, "[WhiteTitle \"{title}\"]")?;
+    }
+    if let Some(title) = &roster.black_title {
+        writeln!(file, "[BlackTitle \"{title}\"]")?;
+    }
+    writeln!(file)?;
+
+    let mut movetext = String::new();
+    for (ply, san) in san_moves.iter().enumerate() {
+        if ply % 2 == 0 {
+            movetext.push_str(&format!("{}. ", ply / 2 + 1));
+        }
+        movetext.push_str(san);
+        movetext.push(' ');
+    }
+    movetext.push_str(result);
+    writeln!(file, "{movetext}")?;
+
+    Ok(())
+}
+
+fn or_unknown(value: &str) -> &str {
+    if value.is_empty() {
+        "?"
+    } else {
+        value
+    }
+}